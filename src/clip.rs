@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+use csv::Writer;
+use sysinfo::{Pid, ProcessExt, System, SystemExt};
+
+use crate::metrics::Metric;
+use crate::smaps;
+use crate::snapshot;
+
+/// A single RES/VIRT measurement taken from the probed process.
+///
+/// Clip files are always RES/VIRT/EVENT, regardless of `--metrics`: they exist to show the shape of
+/// a RES excursion around a trigger, not to mirror the main CSV's column selection. The main
+/// CSV rows written by `run()` below do follow `--metrics`.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    res: u64,
+    virt: u64,
+}
+
+/// Samples are taken but not captured until a trigger fires, at which point the last
+/// `clip_window` samples (`pre`) are kept alongside the `post` samples still being collected.
+struct Capture {
+    pre: Vec<Sample>,
+    post: Vec<Sample>,
+}
+
+/// Runs the event-triggered two-speed sampling loop.
+///
+/// `pid` is sampled every `fast_interval_ms`, one row is written to `writer` every
+/// `interval_ms`, and a ring buffer of the last `clip_window` samples is kept so that when a
+/// sample crosses `trigger_res` or grows by more than `trigger_growth_pct` over the previous
+/// one, the `clip_window` samples before and after the event are dumped into their own
+/// `memprobe-$PID-clip-$N.csv` file, keeping only the most recent `max_clips` of them. Clip
+/// files are always RES/VIRT/EVENT, independently of `--metrics`, which only selects the
+/// columns of the main CSV written every `interval_ms`.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    pid: Pid,
+    system: &mut System,
+    writer: &mut Writer<Box<dyn Write>>,
+    interval_ms: u64,
+    fast_interval_ms: u64,
+    trigger_res: Option<u64>,
+    trigger_growth_pct: Option<f64>,
+    clip_window: usize,
+    max_clips: usize,
+    metrics: &[Metric],
+    detailed: bool,
+    snapshot_on_res: Option<u64>,
+    snapshot_repeat: bool,
+) -> anyhow::Result<()> {
+    let mut ring: VecDeque<Sample> = VecDeque::with_capacity(clip_window);
+    let mut previous: Option<Sample> = None;
+    let mut capturing: Option<Capture> = None;
+    let mut next_clip_index: u64 = 0;
+    let slow_tick_ratio = (interval_ms / fast_interval_ms).max(1);
+    let mut tick = 0u64;
+    let mut snapshot_trigger = snapshot_on_res.map(|threshold| snapshot::Trigger::new(threshold, snapshot_repeat));
+
+    while system.refresh_process(pid) {
+        let Some(process) = system.process(pid) else { break };
+        let sample = Sample { res: process.memory(), virt: process.virtual_memory() };
+
+        if tick % slow_tick_ratio == 0 {
+            let mut record: Vec<String> = metrics.iter().map(|metric| metric.read(process)).collect();
+            if detailed {
+                let breakdown = smaps::read(pid);
+                record.extend(
+                    breakdown.map(|b| b.to_record().to_vec()).unwrap_or_else(|| smaps::empty_record().to_vec()),
+                );
+            }
+            writer.write_record(&record).context("when writing a new line into the CSV file")?;
+            writer.flush().context("when flushing the CSV file")?;
+        }
+
+        if let Some(capture) = capturing.as_mut() {
+            capture.post.push(sample);
+            // `post` starts with the triggering sample, so it is complete once it also holds the
+            // `clip_window` samples that follow the event.
+            if capture.post.len() > clip_window {
+                let Capture { pre, post } = capturing.take().unwrap();
+                write_clip(pid, next_clip_index, &pre, &post, max_clips)?;
+                next_clip_index += 1;
+            }
+        } else if is_triggered(sample, previous, trigger_res, trigger_growth_pct) {
+            capturing = Some(Capture { pre: ring.iter().copied().collect(), post: vec![sample] });
+        }
+
+        if ring.len() == clip_window {
+            ring.pop_front();
+        }
+        ring.push_back(sample);
+
+        if let Some(trigger) = snapshot_trigger.as_mut() {
+            if trigger.check(sample.res) {
+                snapshot::capture(pid, tick);
+            }
+        }
+
+        previous = Some(sample);
+        tick += 1;
+        thread::sleep(Duration::from_millis(fast_interval_ms));
+    }
+
+    Ok(())
+}
+
+fn is_triggered(
+    sample: Sample,
+    previous: Option<Sample>,
+    trigger_res: Option<u64>,
+    trigger_growth_pct: Option<f64>,
+) -> bool {
+    if let Some(threshold) = trigger_res {
+        if sample.res >= threshold {
+            return true;
+        }
+    }
+
+    if let (Some(pct), Some(previous)) = (trigger_growth_pct, previous) {
+        if previous.res > 0 {
+            let growth_pct = (sample.res as f64 - previous.res as f64) / previous.res as f64 * 100.0;
+            if growth_pct >= pct {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+fn write_clip(
+    pid: Pid,
+    index: u64,
+    pre: &[Sample],
+    post: &[Sample],
+    max_clips: usize,
+) -> anyhow::Result<()> {
+    let path = PathBuf::from(format!("memprobe-{}-clip-{}.csv", pid, index));
+    let mut writer = Writer::from_path(&path)
+        .with_context(|| format!("trying to create `{}`", path.display()))?;
+
+    writer
+        .write_record(&["RES", "VIRT", "EVENT"])
+        .context("when writing the headers into the clip file")?;
+    for (i, sample) in pre.iter().chain(post).enumerate() {
+        let event = i == pre.len();
+        writer
+            .write_record(&[sample.res.to_string(), sample.virt.to_string(), event.to_string()])
+            .context("when writing a row into the clip file")?;
+    }
+    writer.flush().context("when flushing the clip file")?;
+
+    prune_old_clips(pid, index, max_clips);
+
+    Ok(())
+}
+
+/// Keeps only the most recent `max_clips` clip files, deleting the oldest ones.
+fn prune_old_clips(pid: Pid, latest_index: u64, max_clips: usize) {
+    let max_clips = max_clips as u64;
+    if latest_index + 1 <= max_clips {
+        return;
+    }
+    for index in 0..(latest_index + 1 - max_clips) {
+        let _ = fs::remove_file(format!("memprobe-{}-clip-{}.csv", pid, index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(res: u64, virt: u64) -> Sample {
+        Sample { res, virt }
+    }
+
+    #[test]
+    fn triggers_on_absolute_threshold() {
+        assert!(is_triggered(sample(200, 0), None, Some(100), None));
+        assert!(!is_triggered(sample(50, 0), None, Some(100), None));
+    }
+
+    #[test]
+    fn triggers_on_growth_percentage() {
+        let previous = sample(100, 0);
+        assert!(is_triggered(sample(200, 0), Some(previous), None, Some(50.0)));
+        assert!(!is_triggered(sample(120, 0), Some(previous), None, Some(50.0)));
+    }
+
+    #[test]
+    fn zero_previous_res_does_not_divide_by_zero_or_false_trigger() {
+        let previous = sample(0, 0);
+        assert!(!is_triggered(sample(100, 0), Some(previous), None, Some(50.0)));
+    }
+
+    #[test]
+    fn no_trigger_without_any_threshold_configured() {
+        assert!(!is_triggered(sample(u64::MAX, u64::MAX), Some(sample(0, 0)), None, None));
+    }
+
+    #[test]
+    fn prune_old_clips_deletes_exactly_the_oldest_excess_indices() {
+        let pid: Pid = "999001".parse().unwrap();
+        let paths: Vec<PathBuf> =
+            (0..=4u64).map(|i| PathBuf::from(format!("memprobe-{}-clip-{}.csv", pid, i))).collect();
+        for path in &paths {
+            fs::write(path, "test").unwrap();
+        }
+
+        prune_old_clips(pid, 4, 2);
+
+        assert!(!paths[0].exists());
+        assert!(!paths[1].exists());
+        assert!(!paths[2].exists());
+        assert!(paths[3].exists());
+        assert!(paths[4].exists());
+
+        for path in &paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn prune_old_clips_with_max_clips_zero_deletes_the_clip_just_written() {
+        let pid: Pid = "999002".parse().unwrap();
+        let path = PathBuf::from(format!("memprobe-{}-clip-0.csv", pid));
+        fs::write(&path, "test").unwrap();
+
+        // Documents present behavior: with --max-clips 0, the clip just written is deleted
+        // along with the rest, since there's nothing distinguishing "just written" from "old".
+        prune_old_clips(pid, 0, 0);
+
+        assert!(!path.exists());
+    }
+}
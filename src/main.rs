@@ -9,6 +9,16 @@ use clap::Parser;
 use csv::Writer;
 use sysinfo::{Pid, ProcessExt, System, SystemExt};
 
+mod clip;
+mod metrics;
+mod resolve;
+mod smaps;
+mod snapshot;
+mod tree;
+
+use metrics::Metric;
+use resolve::NameQuery;
+
 /// A tool to probe the memory usage of a program
 ///
 /// You can run this command on Linux:
@@ -16,11 +26,17 @@ use sysinfo::{Pid, ProcessExt, System, SystemExt};
 ///
 /// Or this one on mac OS:
 ///     memprobe $(pgrep firefox)
+///
+/// Or resolve the target by name instead of wrangling a PID yourself:
+///     memprobe --name firefox
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// The Process ID to measure the memory usage of
-    pid: Pid,
+    ///
+    /// Mutually exclusive with `--name`/`--exact-name`.
+    #[arg(conflicts_with_all = ["name", "exact_name"])]
+    pid: Option<Pid>,
 
     /// The interval, in milliseconds, to wait between two memory usage probings
     #[arg(long, default_value_t = 250)]
@@ -35,30 +51,335 @@ struct Args {
     /// The default path is `./memprobe-$PID.csv`.
     #[arg(long)]
     output_file: Option<PathBuf>,
+
+    /// Enables two-speed sampling: poll at `fast_interval_ms`, keeping a ring buffer of the
+    /// most recent samples, instead of only at `interval_ms`
+    #[arg(long)]
+    fast_interval_ms: Option<u64>,
+
+    /// An absolute RES threshold, in bytes, above which a clip of the ring buffer is dumped
+    ///
+    /// Only takes effect when `--fast-interval-ms` is set.
+    #[arg(long, requires = "fast_interval_ms")]
+    trigger_res: Option<u64>,
+
+    /// A growth, in percent, between two consecutive fast samples that triggers a clip dump
+    ///
+    /// Only takes effect when `--fast-interval-ms` is set.
+    #[arg(long, requires = "fast_interval_ms")]
+    trigger_growth_pct: Option<f64>,
+
+    /// The number of samples to keep before and after a triggering event in a clip file
+    #[arg(long, default_value_t = 50, requires = "fast_interval_ms")]
+    clip_window: usize,
+
+    /// The maximum number of clip files to keep on disk, oldest ones are deleted first
+    #[arg(long, default_value_t = 10, requires = "fast_interval_ms")]
+    max_clips: usize,
+
+    /// Aggregate the recorded metrics of the probed process and all of its transitive children
+    #[arg(long, conflicts_with = "fast_interval_ms")]
+    tree: bool,
+
+    /// Additionally write one column per metric for the probed process and each of its children
+    ///
+    /// The column layout is fixed from the process tree observed at startup, ordered by PID.
+    /// The aggregate columns are re-walked every tick and do track children spawned
+    /// afterwards, but those new children don't get their own per-process columns, and a
+    /// child that exits leaves its columns blank rather than shifting the layout. Not
+    /// compatible with `--follow`, since a followed replacement process generally has a
+    /// different set of children and would require a different column layout.
+    #[arg(long, requires = "tree", conflicts_with = "follow")]
+    per_process: bool,
+
+    /// A comma-separated list of metrics to record
+    ///
+    /// Available metrics: res, virt, cpu, disk_read, disk_write, status, threads. Selects the
+    /// columns of the main CSV only; clip files produced by `--fast-interval-ms` are always
+    /// RES/VIRT/EVENT regardless of this setting.
+    #[arg(long, default_value = "res,virt")]
+    metrics: String,
+
+    /// Add a finer-grained PSS/SHARED/PRIVATE/SWAP breakdown, parsed from
+    /// `/proc/<pid>/smaps_rollup` (Linux only)
+    ///
+    /// Falls back to summing `/proc/<pid>/smaps` when the rollup file is absent. On non-Linux
+    /// platforms the columns are left empty and a one-time warning is printed to stderr.
+    #[arg(long)]
+    detailed: bool,
+
+    /// Capture a snapshot of the process's memory maps the first time RES crosses this
+    /// threshold, in bytes
+    ///
+    /// Saves `/proc/<pid>/maps` and `/proc/<pid>/smaps` to a
+    /// `memprobe-$PID-snapshot-$ts-$sample_index.txt` sidecar file (Linux only).
+    #[arg(long)]
+    snapshot_on_res: Option<u64>,
+
+    /// Capture a new snapshot every time RES crosses `--snapshot-on-res`, instead of only the
+    /// first time
+    #[arg(long, requires = "snapshot_on_res")]
+    snapshot_repeat: bool,
+
+    /// Match processes whose name contains this substring (case-insensitive), instead of a PID
+    ///
+    /// Mutually exclusive with the positional `pid` and with `--exact-name`.
+    #[arg(long, conflicts_with_all = ["pid", "exact_name"])]
+    name: Option<String>,
+
+    /// Match the process whose name is exactly this, instead of a PID
+    ///
+    /// Mutually exclusive with the positional `pid` and with `--name`.
+    #[arg(long, conflicts_with_all = ["pid", "name"])]
+    exact_name: Option<String>,
+
+    /// How to break ties when `--name`/`--exact-name` matches more than one process
+    ///
+    /// Requires `--name` or `--exact-name`.
+    #[arg(long)]
+    pick: Option<String>,
+
+    /// When the matched process exits, re-resolve `--name`/`--exact-name` and keep probing its
+    /// replacement into the same CSV, with a `PID` column marking the transition
+    ///
+    /// Requires `--name` or `--exact-name`, and isn't compatible with `--fast-interval-ms` or
+    /// `--per-process`.
+    #[arg(long, conflicts_with_all = ["fast_interval_ms", "per_process"])]
+    follow: bool,
 }
 
 fn main() -> anyhow::Result<()> {
-    let Args { pid, interval_ms, stdout, output_file } = Args::parse();
+    let Args {
+        pid,
+        interval_ms,
+        stdout,
+        output_file,
+        fast_interval_ms,
+        trigger_res,
+        trigger_growth_pct,
+        clip_window,
+        max_clips,
+        tree,
+        per_process,
+        metrics,
+        detailed,
+        snapshot_on_res,
+        snapshot_repeat,
+        name,
+        exact_name,
+        pick,
+        follow,
+    } = Args::parse();
+
+    let metrics = metrics::parse_list(&metrics).context("when parsing --metrics")?;
+    let pick = pick.as_deref().map(str::parse).transpose().context("when parsing --pick")?;
+
+    if fast_interval_ms == Some(0) {
+        anyhow::bail!("--fast-interval-ms must be greater than zero");
+    }
+
+    let name_query = match (name, exact_name) {
+        (Some(name), None) => Some(NameQuery::Substring(name)),
+        (None, Some(exact_name)) => Some(NameQuery::Exact(exact_name)),
+        (None, None) => None,
+        (Some(_), Some(_)) => unreachable!("--name and --exact-name are mutually exclusive"),
+    };
+
+    if follow && name_query.is_none() {
+        anyhow::bail!("--follow requires --name or --exact-name");
+    }
+
+    if pick.is_some() && name_query.is_none() {
+        anyhow::bail!("--pick requires --name or --exact-name");
+    }
 
     let mut system = System::new();
-    system.refresh_process(pid);
+    system.refresh_processes();
+
+    let mut pid = match (pid, &name_query) {
+        (Some(pid), None) => pid,
+        (None, Some(query)) => resolve::resolve(&system, query, pick)?,
+        (None, None) => anyhow::bail!("either a PID, --name, or --exact-name is required"),
+        (Some(_), Some(_)) => unreachable!("the positional pid and --name/--exact-name are mutually exclusive"),
+    };
+
+    let mut pids = if tree { tree::descendants(&system, pid) } else { vec![pid] };
 
     let mut writer = writer_from_args(pid, stdout, output_file)
         .map(Writer::from_writer)
         .context("when creating the CSV file")?;
 
-    writer.write_record(&["RES", "VIRT"]).context("when writing the headers into the CSV file")?;
-
-    while system.refresh_process(pid) {
-        if let Some(process) = system.process(pid) {
-            let memory = process.memory();
-            let virtual_memory = process.virtual_memory();
-            writer
-                .write_record(&[memory.to_string(), virtual_memory.to_string()])
-                .context("when writing a new line into the CSV file")?;
-            writer.flush().context("when flushing the CSV file")?;
-            thread::sleep(Duration::from_millis(interval_ms));
+    let mut header: Vec<String> = Vec::new();
+    if follow {
+        header.push("PID".to_string());
+    }
+    header.extend(metrics.iter().map(|metric| metric.header().to_string()));
+    if detailed {
+        header.extend(smaps::SmapsBreakdown::HEADER.map(String::from));
+    }
+    if per_process {
+        for p in &pids {
+            for metric in &metrics {
+                header.push(format!("PID_{}_{}", p, metric.header()));
+            }
+            if detailed {
+                header.extend(smaps::SmapsBreakdown::HEADER.map(|name| format!("PID_{}_{}", p, name)));
+            }
+        }
+    }
+    writer.write_record(&header).context("when writing the headers into the CSV file")?;
+
+    if let Some(fast_interval_ms) = fast_interval_ms {
+        return clip::run(
+            pid,
+            &mut system,
+            &mut writer,
+            interval_ms,
+            fast_interval_ms,
+            trigger_res,
+            trigger_growth_pct,
+            clip_window,
+            max_clips,
+            &metrics,
+            detailed,
+            snapshot_on_res,
+            snapshot_repeat,
+        );
+    }
+
+    loop {
+        run_fixed_interval(
+            pid,
+            &mut system,
+            &mut writer,
+            interval_ms,
+            tree,
+            &pids,
+            per_process,
+            &metrics,
+            detailed,
+            snapshot_on_res,
+            snapshot_repeat,
+            follow,
+        )?;
+
+        if !follow {
+            break;
         }
+
+        let Some(new_pid) = reresolve_with_retries(&mut system, name_query.as_ref().unwrap(), pick) else {
+            eprintln!(
+                "memprobe: warning: --follow gave up on re-resolving {} after {} attempts, stopping",
+                name_query.as_ref().unwrap().describe(),
+                FOLLOW_RETRY_ATTEMPTS,
+            );
+            break;
+        };
+        pid = new_pid;
+        pids = if tree { tree::descendants(&system, pid) } else { vec![pid] };
+    }
+
+    Ok(())
+}
+
+/// How many times `--follow` retries re-resolving the name query before giving up.
+const FOLLOW_RETRY_ATTEMPTS: u32 = 20;
+
+/// Re-resolves `query` after the followed process has exited, retrying with a growing backoff
+/// to ride out the gap between the old process exiting and its replacement starting up.
+///
+/// Returns `None` once `FOLLOW_RETRY_ATTEMPTS` have all failed to find a match.
+fn reresolve_with_retries(system: &mut System, query: &NameQuery, pick: Option<resolve::Pick>) -> Option<Pid> {
+    for attempt in 0..FOLLOW_RETRY_ATTEMPTS {
+        system.refresh_processes();
+        if let Ok(pid) = resolve::resolve(system, query, pick) {
+            return Some(pid);
+        }
+        thread::sleep(Duration::from_millis(200 * (attempt as u64 + 1)));
+    }
+    None
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_fixed_interval(
+    pid: Pid,
+    system: &mut System,
+    writer: &mut Writer<Box<dyn Write>>,
+    interval_ms: u64,
+    tree: bool,
+    per_process_pids: &[Pid],
+    per_process: bool,
+    metrics: &[Metric],
+    detailed: bool,
+    snapshot_on_res: Option<u64>,
+    snapshot_repeat: bool,
+    follow: bool,
+) -> anyhow::Result<()> {
+    let mut snapshot_trigger = snapshot_on_res.map(|threshold| snapshot::Trigger::new(threshold, snapshot_repeat));
+    let mut sample_index = 0u64;
+
+    loop {
+        let alive = if tree {
+            system.refresh_processes();
+            system.process(pid).is_some()
+        } else {
+            system.refresh_process(pid)
+        };
+        if !alive {
+            break;
+        }
+
+        // Re-walked every tick so children spawned after startup are reflected in the
+        // aggregate columns below; `per_process_pids` stays frozen for the column layout.
+        let current_pids = if tree { tree::descendants(system, pid) } else { Vec::new() };
+
+        let res = if tree {
+            current_pids.iter().filter_map(|&p| system.process(p)).map(|process| process.memory()).sum()
+        } else {
+            system.process(pid).expect("checked above").memory()
+        };
+
+        let mut record = if follow { vec![pid.to_string()] } else { Vec::new() };
+        record.extend(if tree {
+            tree::aggregate_metrics(system, &current_pids, metrics, pid)
+        } else {
+            let process = system.process(pid).expect("checked above");
+            metrics.iter().map(|metric| metric.read(process)).collect()
+        });
+
+        if detailed {
+            let breakdown = if tree { smaps::aggregate(&current_pids) } else { smaps::read(pid) };
+            record.extend(breakdown.map(|b| b.to_record().to_vec()).unwrap_or_else(|| smaps::empty_record().to_vec()));
+        }
+
+        if per_process {
+            for &p in per_process_pids {
+                match system.process(p) {
+                    Some(process) => {
+                        record.extend(metrics.iter().map(|metric| metric.read(process)));
+                    }
+                    None => record.extend(metrics.iter().map(|_| String::new())),
+                }
+                if detailed {
+                    let breakdown = smaps::read(p);
+                    record.extend(
+                        breakdown.map(|b| b.to_record().to_vec()).unwrap_or_else(|| smaps::empty_record().to_vec()),
+                    );
+                }
+            }
+        }
+
+        writer.write_record(&record).context("when writing a new line into the CSV file")?;
+        writer.flush().context("when flushing the CSV file")?;
+
+        if let Some(trigger) = snapshot_trigger.as_mut() {
+            if trigger.check(res) {
+                snapshot::capture(pid, sample_index);
+            }
+        }
+
+        sample_index += 1;
+        thread::sleep(Duration::from_millis(interval_ms));
     }
 
     Ok(())
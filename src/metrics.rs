@@ -0,0 +1,125 @@
+use std::str::FromStr;
+use std::sync::Once;
+
+use sysinfo::{Pid, Process, ProcessExt};
+
+static THREADS_WARN_ONCE: Once = Once::new();
+
+/// One of the quantities `memprobe` can record about a process, selected through `--metrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Res,
+    Virt,
+    Cpu,
+    DiskRead,
+    DiskWrite,
+    Status,
+    Threads,
+}
+
+impl Metric {
+    /// The CSV column header for this metric.
+    pub fn header(self) -> &'static str {
+        match self {
+            Metric::Res => "RES",
+            Metric::Virt => "VIRT",
+            Metric::Cpu => "CPU",
+            Metric::DiskRead => "DISK_READ",
+            Metric::DiskWrite => "DISK_WRITE",
+            Metric::Status => "STATUS",
+            Metric::Threads => "THREADS",
+        }
+    }
+
+    /// Whether summing this metric across a process subtree produces a meaningful aggregate.
+    pub fn is_summable(self) -> bool {
+        !matches!(self, Metric::Status)
+    }
+
+    /// Reads this metric off a single process into its CSV cell representation.
+    ///
+    /// `Status` is the only non-numeric metric and is read directly; every other metric is
+    /// numeric and falls back to an empty cell if it couldn't be read (e.g. `Threads` off
+    /// `/proc/<pid>/status` on Linux, or unconditionally on non-Linux platforms), rather than
+    /// reusing the process's status string, which would silently corrupt the column.
+    pub fn read(self, process: &Process) -> String {
+        match self {
+            Metric::Status => process.status().to_string(),
+            _ => self.read_numeric(process).map(|value| self.format_numeric(value)).unwrap_or_default(),
+        }
+    }
+
+    /// Reads this metric as a number, for metrics that can meaningfully be summed across a
+    /// process subtree. Returns `None` for `Status`, which isn't numeric.
+    pub fn read_numeric(self, process: &Process) -> Option<f64> {
+        match self {
+            Metric::Res => Some(process.memory() as f64),
+            Metric::Virt => Some(process.virtual_memory() as f64),
+            Metric::Cpu => Some(process.cpu_usage() as f64),
+            Metric::DiskRead => Some(process.disk_usage().total_read_bytes as f64),
+            Metric::DiskWrite => Some(process.disk_usage().total_written_bytes as f64),
+            Metric::Threads => threads(process.pid()).map(|count| count as f64),
+            Metric::Status => None,
+        }
+    }
+
+    /// Formats a number previously produced by `read_numeric` back into a CSV cell.
+    pub fn format_numeric(self, value: f64) -> String {
+        match self {
+            Metric::Cpu => format!("{:.2}", value),
+            _ => (value as u64).to_string(),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "res" => Ok(Metric::Res),
+            "virt" => Ok(Metric::Virt),
+            "cpu" => Ok(Metric::Cpu),
+            "disk_read" => Ok(Metric::DiskRead),
+            "disk_write" => Ok(Metric::DiskWrite),
+            "status" => Ok(Metric::Status),
+            "threads" => Ok(Metric::Threads),
+            other => anyhow::bail!(
+                "unknown metric `{}`, expected one of: res, virt, cpu, disk_read, disk_write, status, threads",
+                other
+            ),
+        }
+    }
+}
+
+/// Parses the comma-separated list of metric names accepted by `--metrics`.
+pub fn parse_list(s: &str) -> anyhow::Result<Vec<Metric>> {
+    s.split(',').map(|metric| metric.trim().parse()).collect()
+}
+
+/// The number of threads of `pid`, read from `/proc/<pid>/status` on Linux, or `None` if it
+/// couldn't be read (e.g. on a non-Linux platform, or if the proc file isn't readable).
+#[cfg(target_os = "linux")]
+fn threads(pid: Pid) -> Option<u64> {
+    let result = std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| status.lines().find_map(|line| line.strip_prefix("Threads:")?.trim().parse().ok()));
+    if result.is_none() {
+        THREADS_WARN_ONCE.call_once(|| {
+            eprintln!(
+                "memprobe: warning: could not read thread count from /proc/{}/status, \
+                 THREADS column will be empty",
+                pid
+            );
+        });
+    }
+    result
+}
+
+#[cfg(not(target_os = "linux"))]
+fn threads(_pid: Pid) -> Option<u64> {
+    THREADS_WARN_ONCE.call_once(|| {
+        eprintln!("memprobe: warning: --metrics threads is only supported on Linux, THREADS column will be empty");
+    });
+    None
+}
@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use sysinfo::{Pid, Process, ProcessExt, System, SystemExt};
+
+/// How to break ties when `--name`/`--exact-name` matches more than one process.
+#[derive(Debug, Clone, Copy)]
+pub enum Pick {
+    Newest,
+    Oldest,
+    MostMem,
+}
+
+impl FromStr for Pick {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "newest" => Ok(Pick::Newest),
+            "oldest" => Ok(Pick::Oldest),
+            "most-mem" => Ok(Pick::MostMem),
+            other => {
+                anyhow::bail!("unknown --pick strategy `{}`, expected one of: newest, oldest, most-mem", other)
+            }
+        }
+    }
+}
+
+/// A way to match processes by name, as accepted by `--name`/`--exact-name`.
+pub enum NameQuery {
+    /// `--name`: a case-insensitive substring of the process name.
+    Substring(String),
+    /// `--exact-name`: the exact process name.
+    Exact(String),
+}
+
+impl NameQuery {
+    fn matches(&self, process_name: &str) -> bool {
+        match self {
+            NameQuery::Substring(needle) => {
+                process_name.to_lowercase().contains(&needle.to_lowercase())
+            }
+            NameQuery::Exact(name) => process_name == name,
+        }
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            NameQuery::Substring(needle) => format!("`--name {}`", needle),
+            NameQuery::Exact(name) => format!("`--exact-name {}`", name),
+        }
+    }
+}
+
+/// Finds every process whose name matches `query`, sorted by PID.
+fn find(system: &System, query: &NameQuery) -> Vec<Pid> {
+    let mut pids: Vec<Pid> = system
+        .processes()
+        .iter()
+        .filter(|(_, process)| query.matches(process.name()))
+        .map(|(&pid, _)| pid)
+        .collect();
+    pids.sort_unstable();
+    pids
+}
+
+/// Resolves `query` to a single PID.
+///
+/// Errors with the list of candidates when more than one process matches and no `pick`
+/// strategy was given to select one deterministically.
+pub fn resolve(system: &System, query: &NameQuery, pick: Option<Pick>) -> anyhow::Result<Pid> {
+    let candidates = find(system, query);
+
+    match (candidates.as_slice(), pick) {
+        ([], _) => anyhow::bail!("no process matches {}", query.describe()),
+        ([pid], _) => Ok(*pid),
+        (candidates, Some(pick)) => pick_one(system, candidates, pick)
+            .ok_or_else(|| anyhow::anyhow!("no process matches {} anymore", query.describe())),
+        (candidates, None) => anyhow::bail!(
+            "{} processes match {}: {}\nuse --pick newest|oldest|most-mem to select one deterministically",
+            candidates.len(),
+            query.describe(),
+            candidates.iter().map(Pid::to_string).collect::<Vec<_>>().join(", "),
+        ),
+    }
+}
+
+fn pick_one(system: &System, candidates: &[Pid], pick: Pick) -> Option<Pid> {
+    let processes: Vec<(Pid, &Process)> =
+        candidates.iter().filter_map(|&pid| system.process(pid).map(|process| (pid, process))).collect();
+
+    match pick {
+        Pick::Newest => processes.into_iter().max_by_key(|(_, process)| process.start_time()).map(|(pid, _)| pid),
+        Pick::Oldest => processes.into_iter().min_by_key(|(_, process)| process.start_time()).map(|(pid, _)| pid),
+        Pick::MostMem => processes.into_iter().max_by_key(|(_, process)| process.memory()).map(|(pid, _)| pid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_query_is_case_insensitive() {
+        let query = NameQuery::Substring("FireFox".to_string());
+        assert!(query.matches("firefox"));
+        assert!(query.matches("my-firefox-helper"));
+        assert!(!query.matches("chrome"));
+    }
+
+    #[test]
+    fn exact_query_requires_a_full_match() {
+        let query = NameQuery::Exact("firefox".to_string());
+        assert!(query.matches("firefox"));
+        assert!(!query.matches("Firefox"));
+        assert!(!query.matches("firefox-helper"));
+    }
+
+    #[test]
+    fn pick_from_str_accepts_the_three_strategies_and_rejects_others() {
+        assert!(matches!("newest".parse::<Pick>().unwrap(), Pick::Newest));
+        assert!(matches!("oldest".parse::<Pick>().unwrap(), Pick::Oldest));
+        assert!(matches!("most-mem".parse::<Pick>().unwrap(), Pick::MostMem));
+        assert!("fastest".parse::<Pick>().is_err());
+    }
+
+    #[test]
+    fn find_returns_nothing_for_an_unknown_name() {
+        let mut system = System::new();
+        system.refresh_processes();
+        // A name that no process has: no matches, no panics.
+        let query = NameQuery::Exact("definitely-not-a-real-process-name".to_string());
+        assert_eq!(find(&system, &query), Vec::<Pid>::new());
+    }
+
+    #[test]
+    fn pick_one_returns_none_when_no_candidate_is_still_alive() {
+        let system = System::new();
+        let stale_candidates = vec!["999999".parse::<Pid>().unwrap()];
+        assert_eq!(pick_one(&system, &stale_candidates, Pick::Newest), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Once;
+
+use sysinfo::Pid;
+
+static WARN_ONCE: Once = Once::new();
+
+/// A finer-grained memory breakdown parsed from Linux's `/proc/<pid>/smaps_rollup`, in bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmapsBreakdown {
+    pub pss: u64,
+    pub shared: u64,
+    pub private: u64,
+    pub swap: u64,
+}
+
+impl SmapsBreakdown {
+    pub const HEADER: [&'static str; 4] = ["PSS", "SHARED", "PRIVATE", "SWAP"];
+
+    pub fn to_record(self) -> [String; 4] {
+        [
+            self.pss.to_string(),
+            self.shared.to_string(),
+            self.private.to_string(),
+            self.swap.to_string(),
+        ]
+    }
+}
+
+/// Four empty cells, written in place of a `SmapsBreakdown` when it couldn't be read.
+pub fn empty_record() -> [String; 4] {
+    [String::new(), String::new(), String::new(), String::new()]
+}
+
+/// Reads the smaps-based breakdown of `pid`, or `None` if it couldn't be read (e.g. on a
+/// non-Linux platform, or if the proc files aren't readable).
+#[cfg(target_os = "linux")]
+pub fn read(pid: Pid) -> Option<SmapsBreakdown> {
+    read_rollup(pid).or_else(|| read_smaps(pid)).or_else(|| {
+        WARN_ONCE.call_once(|| {
+            eprintln!(
+                "memprobe: warning: could not read /proc/{pid}/smaps_rollup or /proc/{pid}/smaps \
+                 for --detailed, PSS/SHARED/PRIVATE/SWAP columns will be empty"
+            );
+        });
+        None
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read(_pid: Pid) -> Option<SmapsBreakdown> {
+    WARN_ONCE.call_once(|| {
+        eprintln!(
+            "memprobe: warning: --detailed is only supported on Linux, \
+             PSS/SHARED/PRIVATE/SWAP columns will be empty"
+        );
+    });
+    None
+}
+
+/// Sums the smaps-based breakdown of every process in `pids`, or `None` if none could be read.
+pub fn aggregate(pids: &[Pid]) -> Option<SmapsBreakdown> {
+    let mut total = SmapsBreakdown::default();
+    let mut read_any = false;
+
+    for &pid in pids {
+        if let Some(breakdown) = read(pid) {
+            total.pss += breakdown.pss;
+            total.shared += breakdown.shared;
+            total.private += breakdown.private;
+            total.swap += breakdown.swap;
+            read_any = true;
+        }
+    }
+
+    read_any.then_some(total)
+}
+
+#[cfg(target_os = "linux")]
+fn read_rollup(pid: Pid) -> Option<SmapsBreakdown> {
+    let content = std::fs::read_to_string(format!("/proc/{}/smaps_rollup", pid)).ok()?;
+    Some(parse(&content))
+}
+
+#[cfg(target_os = "linux")]
+fn read_smaps(pid: Pid) -> Option<SmapsBreakdown> {
+    let content = std::fs::read_to_string(format!("/proc/{}/smaps", pid)).ok()?;
+    Some(parse(&content))
+}
+
+/// Parses the `Pss:`/`Shared_*:`/`Private_*:`/`Swap:` lines (in kB) out of a smaps(-rollup) file.
+#[cfg(target_os = "linux")]
+fn parse(content: &str) -> SmapsBreakdown {
+    let mut breakdown = SmapsBreakdown::default();
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        let Some(kb) = value.trim().strip_suffix(" kB").and_then(|n| n.trim().parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let bytes = kb * 1024;
+
+        match key {
+            "Pss" => breakdown.pss += bytes,
+            "Shared_Clean" | "Shared_Dirty" => breakdown.shared += bytes,
+            "Private_Clean" | "Private_Dirty" => breakdown.private += bytes,
+            "Swap" => breakdown.swap += bytes,
+            _ => {}
+        }
+    }
+
+    breakdown
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_shared_and_private_lines_across_mappings() {
+        let content = "\
+Pss:                 100 kB
+Shared_Clean:          10 kB
+Shared_Dirty:           5 kB
+Private_Clean:         20 kB
+Private_Dirty:          8 kB
+Swap:                   0 kB
+Pss:                  200 kB
+Shared_Clean:          15 kB
+Private_Dirty:          2 kB
+Swap:                   4 kB
+";
+        let breakdown = parse(content);
+        assert_eq!(breakdown.pss, 300 * 1024);
+        assert_eq!(breakdown.shared, (10 + 5 + 15) * 1024);
+        assert_eq!(breakdown.private, (20 + 8 + 2) * 1024);
+        assert_eq!(breakdown.swap, 4 * 1024);
+    }
+
+    #[test]
+    fn ignores_unmatched_and_malformed_lines() {
+        let content = "\
+Size:                 100 kB
+Rss:                   50 kB
+Pss:                    4 kB
+NotAKeyValueLine
+Locked:              garbage kB
+";
+        let breakdown = parse(content);
+        assert_eq!(breakdown.pss, 4 * 1024);
+        assert_eq!(breakdown.shared, 0);
+        assert_eq!(breakdown.private, 0);
+        assert_eq!(breakdown.swap, 0);
+    }
+
+    #[test]
+    fn empty_content_yields_a_zeroed_breakdown() {
+        let breakdown = parse("");
+        assert_eq!(breakdown.pss, 0);
+        assert_eq!(breakdown.shared, 0);
+        assert_eq!(breakdown.private, 0);
+        assert_eq!(breakdown.swap, 0);
+    }
+}
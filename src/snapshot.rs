@@ -0,0 +1,132 @@
+use sysinfo::Pid;
+
+/// Edge-triggers on a RES threshold crossing, for `--snapshot-on-res`.
+///
+/// Fires once on the first crossing, and on every subsequent crossing when `repeat` is set;
+/// staying above the threshold without dipping back below it only fires once per excursion.
+pub struct Trigger {
+    threshold: u64,
+    repeat: bool,
+    above: bool,
+    fired_once: bool,
+}
+
+impl Trigger {
+    pub fn new(threshold: u64, repeat: bool) -> Self {
+        Self { threshold, repeat, above: false, fired_once: false }
+    }
+
+    /// Feeds a new RES sample, returning whether it should trigger a snapshot capture.
+    pub fn check(&mut self, res: u64) -> bool {
+        let now_above = res >= self.threshold;
+        let rising_edge = now_above && !self.above;
+        self.above = now_above;
+
+        if rising_edge && (self.repeat || !self.fired_once) {
+            self.fired_once = true;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Captures `/proc/<pid>/maps` and `/proc/<pid>/smaps` into a
+/// `memprobe-$PID-snapshot-$timestamp-$sample_index.txt` sidecar file, so it can be correlated
+/// with the CSV. `sample_index` counts the samples taken of `pid`: in the fixed-interval loop
+/// it is the index of the CSV row among that PID's rows, while under `--fast-interval-ms` it
+/// counts fast samples, of which only every `interval_ms / fast_interval_ms`-th is written to
+/// the CSV. `sample_index` is also part of the filename, not just the file contents, so that
+/// two triggers within the same wall-clock second don't collide.
+///
+/// Degrades gracefully: a failure to read or write is reported as a warning on stderr rather
+/// than aborting the probe.
+#[cfg(target_os = "linux")]
+pub fn capture(pid: Pid, sample_index: u64) {
+    let maps = match std::fs::read_to_string(format!("/proc/{}/maps", pid)) {
+        Ok(maps) => maps,
+        Err(err) => {
+            eprintln!("memprobe: warning: could not read /proc/{}/maps for snapshot: {}", pid, err);
+            return;
+        }
+    };
+    let smaps = match std::fs::read_to_string(format!("/proc/{}/smaps", pid)) {
+        Ok(smaps) => smaps,
+        Err(err) => {
+            eprintln!("memprobe: warning: could not read /proc/{}/smaps for snapshot: {}", pid, err);
+            return;
+        }
+    };
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    // `sample_index` is included because `--snapshot-repeat` is meant to be paired with fast
+    // polling, so two triggers can easily land within the same wall-clock second and would
+    // otherwise silently overwrite each other's file.
+    let path =
+        std::path::PathBuf::from(format!("memprobe-{}-snapshot-{}-{}.txt", pid, timestamp, sample_index));
+
+    if let Err(err) = write_snapshot(&path, pid, sample_index, &maps, &smaps) {
+        eprintln!("memprobe: warning: could not write snapshot `{}`: {}", path.display(), err);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn write_snapshot(
+    path: &std::path::Path,
+    pid: Pid,
+    sample_index: u64,
+    maps: &str,
+    smaps: &str,
+) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut file =
+        std::fs::OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+    writeln!(file, "# memprobe snapshot of PID {} at sample #{}", pid, sample_index)?;
+    writeln!(file, "\n## /proc/{}/maps\n", pid)?;
+    write!(file, "{}", maps)?;
+    writeln!(file, "\n## /proc/{}/smaps\n", pid)?;
+    write!(file, "{}", smaps)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn capture(_pid: Pid, _sample_index: u64) {
+    static WARN_ONCE: std::sync::Once = std::sync::Once::new();
+    WARN_ONCE.call_once(|| {
+        eprintln!("memprobe: warning: --snapshot-on-res is only supported on Linux");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_once_on_the_rising_edge_without_repeat() {
+        let mut trigger = Trigger::new(100, false);
+        assert!(!trigger.check(50));
+        assert!(trigger.check(100));
+        assert!(!trigger.check(150));
+        assert!(!trigger.check(90));
+        assert!(!trigger.check(150), "without --repeat, a second crossing must not fire again");
+    }
+
+    #[test]
+    fn repeat_fires_on_every_rising_edge() {
+        let mut trigger = Trigger::new(100, true);
+        assert!(trigger.check(150));
+        assert!(!trigger.check(200), "staying above the threshold must not re-fire");
+        assert!(!trigger.check(50));
+        assert!(trigger.check(150), "dipping below and crossing again must re-fire with --repeat");
+    }
+
+    #[test]
+    fn reaching_exactly_the_threshold_counts_as_above() {
+        let mut trigger = Trigger::new(100, false);
+        assert!(trigger.check(100));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use sysinfo::{Pid, ProcessExt, System, SystemExt};
+
+use crate::metrics::Metric;
+
+/// Returns `root` and every transitive child of `root`, sorted by PID.
+pub fn descendants(system: &System, root: Pid) -> Vec<Pid> {
+    let mut children_of: BTreeMap<Pid, Vec<Pid>> = BTreeMap::new();
+    for (&pid, process) in system.processes() {
+        if let Some(parent) = process.parent() {
+            children_of.entry(parent).or_default().push(pid);
+        }
+    }
+
+    let mut pids = vec![root];
+    let mut stack = vec![root];
+    while let Some(pid) = stack.pop() {
+        if let Some(children) = children_of.get(&pid) {
+            for &child in children {
+                pids.push(child);
+                stack.push(child);
+            }
+        }
+    }
+
+    pids.sort_unstable();
+    pids.dedup();
+    pids
+}
+
+/// Reads `metrics` aggregated over every process in `pids` that is still alive in `system`.
+///
+/// Metrics for which summing doesn't make sense (e.g. `Status`) are instead read from `root`.
+pub fn aggregate_metrics(system: &System, pids: &[Pid], metrics: &[Metric], root: Pid) -> Vec<String> {
+    metrics
+        .iter()
+        .map(|&metric| {
+            if metric.is_summable() {
+                let total: f64 = pids
+                    .iter()
+                    .filter_map(|pid| system.process(*pid))
+                    .filter_map(|process| metric.read_numeric(process))
+                    .sum();
+                metric.format_numeric(total)
+            } else {
+                system.process(root).map(|process| metric.read(process)).unwrap_or_default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn descendants_includes_direct_children_sorted_and_deduped() {
+        use std::process::Command;
+        use std::time::{Duration, Instant};
+
+        let mut shell = Command::new("sh")
+            .arg("-c")
+            .arg("sleep 5 & sleep 5 & wait")
+            .spawn()
+            .expect("failed to spawn test child process");
+        let root: Pid = shell.id().to_string().parse().unwrap();
+
+        let mut system = System::new();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let pids = loop {
+            system.refresh_processes();
+            let pids = descendants(&system, root);
+            if pids.len() >= 3 || Instant::now() >= deadline {
+                break pids;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+
+        let _ = shell.kill();
+        let _ = shell.wait();
+
+        assert!(pids.contains(&root));
+        assert_eq!(pids.len(), 3, "expected the shell and its two `sleep` children: {:?}", pids);
+        let mut sorted_deduped = pids.clone();
+        sorted_deduped.sort_unstable();
+        sorted_deduped.dedup();
+        assert_eq!(pids, sorted_deduped);
+    }
+
+    #[test]
+    fn descendants_always_includes_the_root() {
+        let mut system = System::new();
+        system.refresh_processes();
+
+        let root: Pid = std::process::id().to_string().parse().unwrap();
+        // The current test process can have children of its own (other tests spawn some), so
+        // only the root's presence is checked.
+        let pids = descendants(&system, root);
+        assert!(pids.contains(&root));
+    }
+
+    #[test]
+    fn aggregate_metrics_sums_summable_and_reads_non_summable_from_root() {
+        let mut system = System::new();
+        system.refresh_processes();
+
+        let root: Pid = std::process::id().to_string().parse().unwrap();
+        let Some(process) = system.process(root) else {
+            // Not all sandboxes expose the current process through sysinfo; nothing to assert.
+            return;
+        };
+        let expected_res = process.memory();
+        let expected_status = process.status().to_string();
+
+        // Duplicating root in `pids` should double the summed metric, proving it's actually
+        // summing across the slice rather than just reading a single process.
+        let pids = vec![root, root];
+        let result = aggregate_metrics(&system, &pids, &[Metric::Res, Metric::Status], root);
+
+        assert_eq!(result[0], (expected_res * 2).to_string());
+        assert_eq!(result[1], expected_status);
+    }
+}